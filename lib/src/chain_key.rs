@@ -0,0 +1,82 @@
+use near_sdk::{ext_contract, json_types::U64, AccountId, PromiseOrValue, PublicKey};
+use near_sdk_contract_tools::nft::TokenId;
+
+/// Standard interface for a token representing a key manageable through an underlying signer.
+pub trait ChainKeyToken {
+    /// Signs `payload` for the key derived at `path` (scoped under `token_id`), requiring a
+    /// deposit of at least 1 yoctoNEAR; whatever the signer does not spend of it is refunded.
+    /// The caller must be the token owner, or hold an approval that covers
+    /// `path` and has not expired.
+    #[allow(clippy::wrong_self_convention)]
+    fn ckt_sign_hash(
+        &mut self,
+        token_id: TokenId,
+        path: Option<String>,
+        payload: Vec<u8>,
+        approval_id: Option<u32>,
+    ) -> PromiseOrValue<String>;
+
+    /// Returns the public key derived at `path` for `token_id`.
+    fn ckt_public_key_for(
+        &mut self,
+        token_id: TokenId,
+        path: Option<String>,
+    ) -> PromiseOrValue<PublicKey>;
+
+    /// Returns the OID of the signature scheme `token_id` was minted under, or the default
+    /// scheme's OID when `token_id` is `None`.
+    fn ckt_scheme_oid(&self, token_id: Option<TokenId>) -> String;
+}
+
+/// Delegated-approval extension to [`ChainKeyToken`]: lets a token owner authorize another
+/// account to call `ckt_sign_hash` on its behalf, optionally scoped to a derivation-path prefix
+/// and/or an expiry.
+pub trait ChainKeyTokenApproval {
+    fn ckt_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        path_prefix: Option<String>,
+        expires_at: Option<u64>,
+    ) -> u32;
+
+    fn ckt_approve_call(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        path_prefix: Option<String>,
+        expires_at: Option<u64>,
+        msg: Option<String>,
+    ) -> PromiseOrValue<Option<u32>>;
+
+    fn ckt_revoke(&mut self, token_id: TokenId, account_id: AccountId);
+
+    fn ckt_revoke_call(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> PromiseOrValue<()>;
+
+    fn ckt_revoke_all(&mut self, token_id: TokenId) -> U64;
+
+    fn ckt_approval_id_for(&self, token_id: TokenId, account_id: AccountId) -> Option<u32>;
+}
+
+/// Implemented by contracts that want to be notified when they are granted or lose a chain-key
+/// approval via `ckt_approve_call`/`ckt_revoke_call`.
+#[ext_contract(ext_chain_key_token_approval_receiver)]
+pub trait ChainKeyTokenApprovalReceiver {
+    /// Returns `false` to keep the approval; returning `true`, or failing, revokes it immediately.
+    fn ckt_on_approved(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        approval_id: u32,
+        path_prefix: Option<String>,
+        expires_at: Option<u64>,
+        msg: String,
+    ) -> bool;
+
+    fn ckt_on_revoked(&mut self, owner_id: AccountId, token_id: TokenId, approval_id: u32, msg: String);
+}
@@ -0,0 +1,32 @@
+pub mod chain_key;
+pub mod signer;
+pub mod utils;
+
+use near_sdk::env;
+
+/// Convenience trait for turning internal `Result`/`Option` failures into contract panics,
+/// so call sites don't need to hand-roll a `match` just to reject with a message.
+pub trait Rejectable<T> {
+    fn unwrap_or_reject(self) -> T;
+    fn expect_or_reject(self, message: &str) -> T;
+}
+
+impl<T, E: core::fmt::Debug> Rejectable<T> for Result<T, E> {
+    fn unwrap_or_reject(self) -> T {
+        self.unwrap_or_else(|err| env::panic_str(&format!("{err:?}")))
+    }
+
+    fn expect_or_reject(self, message: &str) -> T {
+        self.unwrap_or_else(|_| env::panic_str(message))
+    }
+}
+
+impl<T> Rejectable<T> for Option<T> {
+    fn unwrap_or_reject(self) -> T {
+        self.unwrap_or_else(|| env::panic_str("Called `unwrap_or_reject` on a `None` value"))
+    }
+
+    fn expect_or_reject(self, message: &str) -> T {
+        self.unwrap_or_else(|| env::panic_str(message))
+    }
+}
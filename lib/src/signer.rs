@@ -0,0 +1,76 @@
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    ext_contract,
+    serde::{Deserialize, Serialize},
+    AccountId, NearToken, PublicKey,
+};
+
+/// Signature scheme a derived key is signed under; each has its own derivation domain.
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Scheme {
+    Secp256k1,
+    Ed25519,
+    Secp256r1,
+}
+
+impl Scheme {
+    /// OID identifying this curve.
+    /// See: <https://oidref.com/1.3.132.0.10>, <https://oidref.com/1.3.101.112>,
+    /// <https://oidref.com/1.2.840.10045.3.1.7>
+    pub fn oid(self) -> &'static str {
+        match self {
+            Self::Secp256k1 => "1.3.132.0.10",
+            Self::Ed25519 => "1.3.101.112",
+            Self::Secp256r1 => "1.2.840.10045.3.1.7",
+        }
+    }
+}
+
+/// Request to sign `payload` for the key derived at `path`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignRequest {
+    pub payload: Vec<u8>,
+    pub path: String,
+    pub key_version: u32,
+    pub scheme: Scheme,
+}
+
+impl SignRequest {
+    pub fn new(payload: Vec<u8>, path: String, key_version: u32, scheme: Scheme) -> Self {
+        Self {
+            payload,
+            path,
+            key_version,
+            scheme,
+        }
+    }
+}
+
+/// Signature produced by the signer contract for a [`SignRequest`].
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignResult {
+    pub big_r: String,
+    pub s: String,
+    pub recovery_id: u8,
+}
+
+#[ext_contract(ext_signer)]
+pub trait Signer {
+    fn latest_key_version(&self) -> u32;
+
+    /// Signs `request`, returning the signature alongside the amount of the attached
+    /// deposit the signer actually spent, so the caller can refund the rest.
+    fn sign(&mut self, request: SignRequest) -> (SignResult, NearToken);
+
+    fn derived_public_key(
+        &self,
+        path: String,
+        predecessor: Option<AccountId>,
+        scheme: Scheme,
+    ) -> PublicKey;
+}
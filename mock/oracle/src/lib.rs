@@ -1,38 +1,260 @@
-use lib::oracle::{decode_pyth_price_id, PYTH_PRICE_ID_ETH_USD, PYTH_PRICE_ID_NEAR_USD};
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
-    near_bindgen,
+    collections::UnorderedMap,
+    env, near_bindgen, require, AccountId, BorshStorageKey, PanicOnDefault,
 };
+use near_sdk_contract_tools::rbac::Rbac;
 
-#[derive(BorshSerialize, BorshDeserialize, Default, Debug)]
+/// Length in bytes of a recovered guardian's Ethereum-style address.
+const GUARDIAN_ADDRESS_LEN: usize = 20;
+/// Length in bytes of a single guardian signature: `[guardian_index, r, s, recovery_id]`.
+const SIGNATURE_LEN: usize = 66;
+/// Default maximum ratio (in basis points of price) a feed's confidence interval may reach
+/// before it is considered too uncertain to quote gas from.
+const DEFAULT_MAX_CONF_RATIO_BPS: u64 = 1000;
+/// Default maximum age, in seconds, a price may have before [`Contract::get_price`] rejects it.
+const DEFAULT_MAX_AGE_SEC: u64 = 60;
+
+type GuardianAddress = [u8; GUARDIAN_ADDRESS_LEN];
+
+#[derive(BorshSerialize, BorshDeserialize, BorshStorageKey)]
+enum StorageKey {
+    Prices,
+}
+
+/// Roles recognized by [`Contract`]'s RBAC.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Can rotate the stored guardian set.
+    Dao,
+}
+
+/// A Pyth price stored in contract state, keyed by [`pyth::state::PriceIdentifier`].
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct StoredPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: u64,
+}
+
+impl From<&StoredPrice> for pyth::state::Price {
+    fn from(stored: &StoredPrice) -> Self {
+        Self {
+            price: stored.price.into(),
+            conf: stored.conf.into(),
+            expo: stored.expo,
+            publish_time: stored.publish_time,
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault, Debug, Rbac)]
+#[rbac(roles = "Role")]
 #[near_bindgen]
-pub struct Contract {}
+pub struct Contract {
+    owner: AccountId,
+    guardian_set_index: u32,
+    guardian_addresses: Vec<GuardianAddress>,
+    max_conf_ratio_bps: u64,
+    prices: UnorderedMap<[u8; 32], StoredPrice>,
+}
 
 #[near_bindgen]
 impl Contract {
-    pub fn get_price(
+    #[init]
+    pub fn new(
+        owner: AccountId,
+        guardian_set_index: u32,
+        guardian_addresses: Vec<GuardianAddress>,
+    ) -> Self {
+        let mut contract = Self {
+            owner: owner.clone(),
+            guardian_set_index,
+            guardian_addresses,
+            max_conf_ratio_bps: DEFAULT_MAX_CONF_RATIO_BPS,
+            prices: UnorderedMap::new(StorageKey::Prices),
+        };
+
+        contract.add_role(&owner, &Role::Dao);
+
+        contract
+    }
+
+    /// Rotates the guardian set used to verify future VAAs.
+    pub fn set_guardian_set(
+        &mut self,
+        guardian_set_index: u32,
+        guardian_addresses: Vec<GuardianAddress>,
+    ) {
+        self.require_role(&Role::Dao);
+        self.guardian_set_index = guardian_set_index;
+        self.guardian_addresses = guardian_addresses;
+    }
+
+    /// Accepts a Wormhole-signed VAA carrying a Pyth price update, verifies the guardian
+    /// signatures against the stored guardian set, and stores the decoded price.
+    pub fn update_price(&mut self, vaa: Vec<u8>) {
+        let body = self.verify_vaa(&vaa);
+        let (price_identifier, stored) = parse_price_attestation(body);
+
+        if let Some(existing) = self.prices.get(&price_identifier) {
+            require!(
+                stored.publish_time >= existing.publish_time,
+                "Update is older than the stored price"
+            );
+        }
+
+        self.prices.insert(&price_identifier, &stored);
+    }
+
+    /// Returns the stored price for `price_identifier`, rejecting it if it is older than
+    /// [`DEFAULT_MAX_AGE_SEC`] or if its confidence interval exceeds the configured fraction of
+    /// the price. There is no unguarded path to a stored price: callers that need a different
+    /// staleness bound should use [`Self::get_price_no_older_than`] instead.
+    pub fn get_price(&self, price_identifier: pyth::state::PriceIdentifier) -> pyth::state::Price {
+        self.checked_price(price_identifier, DEFAULT_MAX_AGE_SEC)
+    }
+
+    /// Returns the stored price for `price_identifier`, rejecting it if it is older than
+    /// `max_age_sec` or if its confidence interval exceeds the configured fraction of the price.
+    pub fn get_price_no_older_than(
         &self,
         price_identifier: pyth::state::PriceIdentifier,
-    ) -> Option<pyth::state::Price> {
-        let near_usd = decode_pyth_price_id(PYTH_PRICE_ID_NEAR_USD);
-        let eth_usd = decode_pyth_price_id(PYTH_PRICE_ID_ETH_USD);
-
-        if price_identifier.0 == near_usd {
-            Some(pyth::state::Price {
-                price: 718120242.into(),
-                conf: 420242.into(),
-                expo: -8,
-                publish_time: 1712830518,
-            })
-        } else if price_identifier.0 == eth_usd {
-            Some(pyth::state::Price {
-                price: 357262000000.into(),
-                conf: 135000000.into(),
-                expo: -8,
-                publish_time: 1712830748,
-            })
-        } else {
-            None
+        max_age_sec: u64,
+    ) -> pyth::state::Price {
+        self.checked_price(price_identifier, max_age_sec)
+    }
+
+    /// Looks up the stored price for `price_identifier` and enforces both the staleness bound
+    /// and the confidence-ratio bound, shared by [`Self::get_price`] and
+    /// [`Self::get_price_no_older_than`].
+    fn checked_price(
+        &self,
+        price_identifier: pyth::state::PriceIdentifier,
+        max_age_sec: u64,
+    ) -> pyth::state::Price {
+        let stored = self
+            .prices
+            .get(&price_identifier.0)
+            .unwrap_or_else(|| env::panic_str("No price stored for identifier"));
+
+        let now_sec = env::block_timestamp() / 1_000_000_000;
+        require!(
+            now_sec.saturating_sub(stored.publish_time) <= max_age_sec,
+            "Price is too stale"
+        );
+        require!(
+            u128::from(stored.conf) * 10_000 <= u128::from(stored.price.unsigned_abs()) * u128::from(self.max_conf_ratio_bps),
+            "Price confidence interval is too wide"
+        );
+
+        (&stored).into()
+    }
+
+    pub fn set_max_conf_ratio_bps(&mut self, max_conf_ratio_bps: u64) {
+        self.require_role(&Role::Dao);
+        self.max_conf_ratio_bps = max_conf_ratio_bps;
+    }
+
+    /// Verifies a VAA's guardian signatures and returns its body (the Pyth payload).
+    ///
+    /// A VAA is `version:u8 || guardian_set_index:u32 || sig_count:u8 || sigs || body`, where
+    /// each signature is `guardian_index:u8 || r:32 || s:32 || recovery_id:u8` over
+    /// `keccak256(keccak256(body))`. At least `floor(2/3 * N) + 1` signatures must recover to
+    /// addresses in the stored guardian set, in ascending guardian-index order.
+    fn verify_vaa<'a>(&self, vaa: &'a [u8]) -> &'a [u8] {
+        require!(vaa.len() >= 6, "VAA too short");
+        let version = vaa[0];
+        require!(version == 1, "Unsupported VAA version");
+
+        let guardian_set_index = u32::from_be_bytes(vaa[1..5].try_into().unwrap());
+        require!(
+            guardian_set_index == self.guardian_set_index,
+            "VAA was signed by an unknown guardian set"
+        );
+
+        let sig_count = vaa[5] as usize;
+        let sigs_end = 6 + sig_count * SIGNATURE_LEN;
+        require!(vaa.len() > sigs_end, "VAA missing body");
+        let body = &vaa[sigs_end..];
+
+        let digest: [u8; 32] = env::keccak256(&env::keccak256(body)).try_into().unwrap();
+
+        let quorum = self.guardian_addresses.len() * 2 / 3 + 1;
+        let mut valid_signatures = 0usize;
+        let mut last_guardian_index: Option<u8> = None;
+
+        for sig_index in 0..sig_count {
+            let sig = &vaa[6 + sig_index * SIGNATURE_LEN..6 + (sig_index + 1) * SIGNATURE_LEN];
+            let guardian_index = sig[0];
+            require!(
+                last_guardian_index.map_or(true, |last| guardian_index > last),
+                "Guardian signatures must be in ascending index order"
+            );
+            last_guardian_index = Some(guardian_index);
+
+            let expected_address = self
+                .guardian_addresses
+                .get(guardian_index as usize)
+                .unwrap_or_else(|| env::panic_str("Unknown guardian index"));
+
+            let mut rs = [0u8; 64];
+            rs.copy_from_slice(&sig[1..65]);
+            let recovery_id = sig[65];
+
+            let recovered = recover_guardian_address(&digest, &rs, recovery_id)
+                .unwrap_or_else(|| env::panic_str("Failed to recover guardian signature"));
+            require!(
+                &recovered == expected_address,
+                "Guardian signature does not match guardian set"
+            );
+            valid_signatures += 1;
         }
+
+        require!(
+            valid_signatures >= quorum,
+            "Not enough valid guardian signatures to reach quorum"
+        );
+
+        body
     }
 }
+
+/// Recovers the 20-byte Ethereum-style address of the guardian that produced `signature`
+/// over `digest`.
+fn recover_guardian_address(
+    digest: &[u8; 32],
+    signature: &[u8; 64],
+    recovery_id: u8,
+) -> Option<GuardianAddress> {
+    let public_key = env::ecrecover(digest, signature, recovery_id, true)?;
+    let hash = env::keccak256(&public_key);
+    let mut address = [0u8; GUARDIAN_ADDRESS_LEN];
+    address.copy_from_slice(&hash[12..32]);
+    Some(address)
+}
+
+/// Decodes a Pyth price attestation payload: `price_identifier:32 || price:i64 || conf:u64 ||
+/// expo:i32 || publish_time:u64`, all big-endian.
+fn parse_price_attestation(body: &[u8]) -> ([u8; 32], StoredPrice) {
+    require!(body.len() >= 60, "Malformed price attestation");
+
+    let mut price_identifier = [0u8; 32];
+    price_identifier.copy_from_slice(&body[0..32]);
+
+    let price = i64::from_be_bytes(body[32..40].try_into().unwrap());
+    let conf = u64::from_be_bytes(body[40..48].try_into().unwrap());
+    let expo = i32::from_be_bytes(body[48..52].try_into().unwrap());
+    let publish_time = u64::from_be_bytes(body[52..60].try_into().unwrap());
+
+    (
+        price_identifier,
+        StoredPrice {
+            price,
+            conf,
+            expo,
+            publish_time,
+        },
+    )
+}
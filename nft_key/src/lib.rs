@@ -1,5 +1,5 @@
 use lib::{
-    chain_key::{ext_chain_key_token_approval_receiver, ChainKeyToken, ChainKeyTokenApproval}, signer::{ext_signer, SignRequest, SignResult}, utils::assert_gt_one_yocto_near, Rejectable
+    chain_key::{ext_chain_key_token_approval_receiver, ChainKeyToken, ChainKeyTokenApproval}, signer::{ext_signer, Scheme, SignRequest, SignResult}, utils::assert_gt_one_yocto_near, Rejectable
 };
 use near_sdk::{
     assert_one_yocto, collections::UnorderedMap, env, near, require, AccountId, AccountIdRef, BorshStorageKey, Gas, NearToken, PanicOnDefault, Promise, PromiseError, PromiseOrValue, PublicKey
@@ -7,10 +7,10 @@ use near_sdk::{
 use near_sdk_contract_tools::hook::Hook;
 #[allow(clippy::wildcard_imports)]
 use near_sdk_contract_tools::nft::*;
-
-/// OID for secp256k1 curve.
-/// See: <https://oidref.com/1.3.132.0.10>
-static SCHEME_OID: &str = "1.3.132.0.10";
+use near_sdk_contract_tools::event;
+use near_sdk_contract_tools::pause::{Pause, PauseHook};
+use near_sdk_contract_tools::rbac::Rbac;
+use near_sdk_contract_tools::upgrade::UpgradeHook;
 
 #[derive(Debug, BorshStorageKey)]
 #[near]
@@ -19,44 +19,137 @@ enum StorageKey {
     ApprovalsFor(u32),
 }
 
+/// A delegated signing grant, optionally scoped to a derivation-path prefix and/or an expiry.
+#[derive(Debug, Clone)]
+#[near]
+pub struct Approval {
+    pub approval_id: u32,
+    pub path_prefix: Option<String>,
+    pub expires_at: Option<u64>,
+}
+
 #[derive(Debug)]
 #[near]
 pub struct KeyData {
-    pub approvals: UnorderedMap<AccountId, u32>,
+    pub approvals: UnorderedMap<AccountId, Approval>,
     pub key_version: u32,
+    pub scheme: Scheme,
+}
+
+/// Roles recognized by [`NftKeyContract`]'s RBAC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[near(serializers = [borsh])]
+pub enum Role {
+    /// Can authorize contract upgrades.
+    Dao,
+    /// Can repoint the signer contract and pause/unpause signing operations.
+    SignerAdmin,
 }
 
-#[derive(Debug, PanicOnDefault, NonFungibleToken)]
+#[derive(Debug, PanicOnDefault, NonFungibleToken, Rbac, Pause)]
 #[non_fungible_token(transfer_hook = "Self", burn_hook = "Self")]
+#[rbac(roles = "Role")]
 #[near(contract_state)]
 pub struct NftKeyContract {
     pub next_id: u32,
     pub signer_contract_id: AccountId,
     pub key_data: UnorderedMap<u32, KeyData>,
+    pub owner: AccountId,
 }
 
 fn generate_token_metadata(id: u32) -> TokenMetadata {
     TokenMetadata::new().title(format!("Chain Key Token #{id}"))
 }
 
+/// `ckt` event standard covering the chain-key lifecycle, for indexers and relayers.
+#[event(standard = "ckt", version = "1.0.0")]
+#[derive(Debug, Clone)]
+pub enum CktEvent {
+    Mint(CktMint),
+    Sign(CktSign),
+    Approve(CktApprove),
+    Revoke(CktRevoke),
+    RevokeAll(CktRevokeAll),
+}
+
+#[derive(Debug, Clone, near_sdk::serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CktMint {
+    pub token_id: TokenId,
+    pub owner: AccountId,
+    pub key_version: u32,
+}
+
+#[derive(Debug, Clone, near_sdk::serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CktSign {
+    pub token_id: TokenId,
+    pub path: String,
+    pub caller: AccountId,
+    pub approval_id: Option<u32>,
+}
+
+#[derive(Debug, Clone, near_sdk::serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CktApprove {
+    pub token_id: TokenId,
+    pub account_id: AccountId,
+    pub approval_id: u32,
+}
+
+#[derive(Debug, Clone, near_sdk::serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CktRevoke {
+    pub token_id: TokenId,
+    pub account_id: AccountId,
+    pub approval_id: u32,
+}
+
+#[derive(Debug, Clone, near_sdk::serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CktRevokeAll {
+    pub token_id: TokenId,
+    pub count: u64,
+}
+
+/// Pre-upgrade state layout, for use by [`NftKeyContract::migrate`]: frozen as of before
+/// `KeyData` grew `scheme` and scoped, struct-shaped approvals, so it can still deserialize
+/// tokens minted under the older layout.
+#[derive(near_sdk::borsh::BorshDeserialize)]
+struct OldState {
+    next_id: u32,
+    signer_contract_id: AccountId,
+    key_data: UnorderedMap<u32, OldKeyData>,
+}
+
+/// `KeyData` as it existed before `scheme` and scoped approvals were added.
+#[derive(near_sdk::borsh::BorshDeserialize)]
+struct OldKeyData {
+    approvals: UnorderedMap<AccountId, u32>,
+    key_version: u32,
+}
+
 #[near]
 impl NftKeyContract {
     #[private]
     #[init]
-    pub fn new(signer_contract_id: AccountId) -> Self {
+    pub fn new(signer_contract_id: AccountId, owner: AccountId) -> Self {
         let mut contract = Self {
             next_id: 0,
             signer_contract_id,
             key_data: UnorderedMap::new(StorageKey::KeyData),
+            owner,
         };
 
         contract.set_contract_metadata(&ContractMetadata::new("Chain Key Token", "CKT", None));
+        contract.add_role(&contract.owner.clone(), &Role::Dao);
+        contract.add_role(&contract.owner.clone(), &Role::SignerAdmin);
 
         contract
     }
 
-    #[cfg(feature = "debug")]
     pub fn set_signer_contract_id(&mut self, account_id: AccountId) {
+        self.require_role(&Role::SignerAdmin);
         self.signer_contract_id = account_id;
     }
 
@@ -70,7 +163,7 @@ impl NftKeyContract {
         id
     }
 
-    pub fn mint(&mut self) -> Promise {
+    pub fn mint(&mut self, scheme: Scheme) -> Promise {
         let storage_usage_start = env::storage_usage();
         let id = self.generate_id();
         let predecessor = env::predecessor_account_id();
@@ -83,6 +176,7 @@ impl NftKeyContract {
                 id,
                 predecessor,
                 storage_usage_start,
+                scheme,
             ))
     }
 
@@ -92,6 +186,7 @@ impl NftKeyContract {
         #[serializer(borsh)] id: u32,
         #[serializer(borsh)] predecessor: AccountId,
         #[serializer(borsh)] storage_usage_start: u64,
+        #[serializer(borsh)] scheme: Scheme,
         #[callback_result] result: Result<u32, PromiseError>,
     ) -> u32 {
         let key_version = result.unwrap();
@@ -101,6 +196,7 @@ impl NftKeyContract {
             &KeyData {
                 key_version,
                 approvals: UnorderedMap::new(StorageKey::ApprovalsFor(id)),
+                scheme,
             },
         );
         self.mint_with_metadata(&id.to_string(), &predecessor, &generate_token_metadata(id))
@@ -109,6 +205,13 @@ impl NftKeyContract {
         self.storage_accounting(&predecessor, storage_usage_start)
             .unwrap_or_reject();
 
+        CktEvent::Mint(CktMint {
+            token_id: id.to_string(),
+            owner: predecessor,
+            key_version,
+        })
+        .emit();
+
         id
     }
 
@@ -123,6 +226,12 @@ impl NftKeyContract {
         key_data.approvals.clear();
         self.key_data.insert(&id, &key_data);
 
+        CktEvent::RevokeAll(CktRevokeAll {
+            token_id: token_id.clone(),
+            count: len,
+        })
+        .emit();
+
         len.into()
     }
 }
@@ -137,6 +246,7 @@ impl ChainKeyToken for NftKeyContract {
         payload: Vec<u8>,
         approval_id: Option<u32>,
     ) -> PromiseOrValue<String> {
+        self.require_unpaused();
         assert_gt_one_yocto_near();
 
         let id = token_id.parse().expect_or_reject("Invalid token ID");
@@ -150,29 +260,58 @@ impl ChainKeyToken for NftKeyContract {
             .get(&id)
             .expect_or_reject("Missing data for key");
 
-        require!(
-            Some(&expected_owner_id) == actual_owner_id.as_ref()
-                || key_data
-                    .approvals
-                    .get(&env::predecessor_account_id())
-                    .zip(approval_id)
-                    .map_or(false, |(actual, expected)| actual == expected),
-            "Unauthorized: Caller must be token owner or have valid approval",
-        );
+        if Some(&expected_owner_id) != actual_owner_id.as_ref() {
+            let approval = key_data
+                .approvals
+                .get(&expected_owner_id)
+                .expect_or_reject("Unauthorized: Caller must be token owner or have valid approval");
+
+            require!(
+                Some(approval.approval_id) == approval_id,
+                "Unauthorized: Caller must be token owner or have valid approval"
+            );
+
+            if let Some(path_prefix) = &approval.path_prefix {
+                require!(
+                    path.starts_with(path_prefix.as_str()),
+                    "Approval does not cover this derivation path"
+                );
+            }
+
+            if let Some(expires_at) = approval.expires_at {
+                require!(env::block_timestamp() < expires_at, "Approval has expired");
+            }
+        }
+
+        match key_data.scheme {
+            Scheme::Ed25519 => {}
+            Scheme::Secp256k1 | Scheme::Secp256r1 => {
+                require!(payload.len() == 32, "ECDSA payload must be a 32-byte digest");
+            }
+        }
+
+        let attached_deposit = env::attached_deposit();
 
         PromiseOrValue::Promise(
             ext_signer::ext(self.signer_contract_id.clone())
-                .with_attached_deposit(env::attached_deposit())
+                .with_attached_deposit(attached_deposit)
                 .sign(SignRequest::new(
-                    payload.try_into().unwrap(),
+                    payload,
                     make_path_string(&token_id, &path),
                     key_data.key_version,
+                    key_data.scheme,
                 ))
                 .then(
                     Self::ext(env::current_account_id())
                         .with_static_gas(Self::SIGN_CALLBACK_GAS)
                         .with_unused_gas_weight(0)
-                        .sign_callback(),
+                        .sign_callback(
+                            expected_owner_id,
+                            attached_deposit,
+                            token_id,
+                            path,
+                            approval_id,
+                        ),
                 ),
         )
     }
@@ -182,16 +321,33 @@ impl ChainKeyToken for NftKeyContract {
         token_id: TokenId,
         path: Option<String>,
     ) -> PromiseOrValue<PublicKey> {
+        self.require_unpaused();
         let path = path.unwrap_or_default();
+        let id = token_id.parse().expect_or_reject("Invalid token ID");
+        let key_data = self
+            .key_data
+            .get(&id)
+            .expect_or_reject("Missing data for key");
 
         PromiseOrValue::Promise(
             ext_signer::ext(self.signer_contract_id.clone())
-                .derived_public_key(make_path_string(&token_id, &path), None),
+                .derived_public_key(make_path_string(&token_id, &path), None, key_data.scheme),
         )
     }
 
-    fn ckt_scheme_oid(&self) -> String {
-        SCHEME_OID.to_string()
+    fn ckt_scheme_oid(&self, token_id: Option<TokenId>) -> String {
+        match token_id {
+            Some(token_id) => {
+                let id: u32 = token_id.parse().expect_or_reject("Invalid token ID");
+                self.key_data
+                    .get(&id)
+                    .expect_or_reject("Missing data for key")
+                    .scheme
+                    .oid()
+                    .to_string()
+            }
+            None => Scheme::Secp256k1.oid().to_string(),
+        }
     }
 }
 
@@ -206,17 +362,33 @@ impl NftKeyContract {
     #[must_use]
     pub fn sign_callback(
         &self,
-        #[callback_result] result: Result<SignResult, PromiseError>,
+        #[serializer(borsh)] predecessor: AccountId,
+        #[serializer(borsh)] attached_deposit: NearToken,
+        #[serializer(borsh)] token_id: TokenId,
+        #[serializer(borsh)] path: String,
+        #[serializer(borsh)] approval_id: Option<u32>,
+        #[callback_result] result: Result<(SignResult, NearToken), PromiseError>,
     ) -> SignResult {
-        let deposit = env::attached_deposit();
-        let predecessor = env::predecessor_account_id();
+        let (sign_result, spent) = result.unwrap();
 
-        // TODO: refund only amount not used (signer contract should return the amount used)
-        if deposit > NearToken::from_yoctonear(0) {
-            Promise::new(predecessor).transfer(deposit);
+        require!(
+            spent <= attached_deposit,
+            "Signer spent more than the attached deposit"
+        );
+        let refund = attached_deposit.saturating_sub(spent);
+        if refund > NearToken::from_yoctonear(0) {
+            Promise::new(predecessor.clone()).transfer(refund);
         }
 
-        result.unwrap()
+        CktEvent::Sign(CktSign {
+            token_id,
+            path,
+            caller: predecessor,
+            approval_id,
+        })
+        .emit();
+
+        sign_result
     }
 
     #[private]
@@ -231,9 +403,12 @@ impl NftKeyContract {
             Some(approval_id)
         } else {
             let mut key_data = self.key_data.get(&token_id).unwrap_or_reject();
-            let ejected_id = key_data.approvals.remove(&account_id);
+            let ejected = key_data.approvals.remove(&account_id);
             self.key_data.insert(&token_id, &key_data);
-            require!(ejected_id == Some(approval_id), "Inconsistent approval ID");
+            require!(
+                ejected.map(|a| a.approval_id) == Some(approval_id),
+                "Inconsistent approval ID"
+            );
             None
         }
     }
@@ -246,37 +421,75 @@ impl NftKeyContract {
         require!(actual_owner.as_ref() == Some(predecessor), "Unauthorized only the token owner can perform this action");
     }
 
-    fn approve(&mut self, token_id: u32, account_id: &AccountId) -> u32 {
+    fn approve(
+        &mut self,
+        token_id: u32,
+        account_id: &AccountId,
+        path_prefix: Option<String>,
+        expires_at: Option<u64>,
+    ) -> u32 {
         let approval_id = self.generate_id();
 
         let mut key_data = self
             .key_data
             .get(&token_id)
             .expect_or_reject("Missing data for key");
-        key_data.approvals.insert(account_id, &approval_id);
+        key_data.approvals.insert(
+            account_id,
+            &Approval {
+                approval_id,
+                path_prefix,
+                expires_at,
+            },
+        );
         self.key_data.insert(&token_id, &key_data);
 
+        CktEvent::Approve(CktApprove {
+            token_id: token_id.to_string(),
+            account_id: account_id.clone(),
+            approval_id,
+        })
+        .emit();
+
         approval_id
     }
 
     fn revoke(&mut self, token_id: u32, account_id: &AccountId) -> Option<u32> {
-        self.key_data.get(&token_id).and_then(|mut key_data| {
+        let removed = self.key_data.get(&token_id).and_then(|mut key_data| {
             let removed = key_data.approvals.remove(account_id);
             self.key_data.insert(&token_id, &key_data);
             removed
-        })
+        });
+        let removed = removed.map(|approval| approval.approval_id);
+
+        if let Some(approval_id) = removed {
+            CktEvent::Revoke(CktRevoke {
+                token_id: token_id.to_string(),
+                account_id: account_id.clone(),
+                approval_id,
+            })
+            .emit();
+        }
+
+        removed
     }
 }
 
 #[near]
 impl ChainKeyTokenApproval for NftKeyContract {
     #[payable]
-    fn ckt_approve(&mut self, token_id: TokenId, account_id: AccountId) -> u32 {
+    fn ckt_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        path_prefix: Option<String>,
+        expires_at: Option<u64>,
+    ) -> u32 {
         assert_one_yocto();
         let predecessor = env::predecessor_account_id();
         self.require_is_token_owner(&predecessor, &token_id);
         let id = token_id.parse().expect_or_reject("Invalid token ID");
-        self.approve(id, &account_id)
+        self.approve(id, &account_id, path_prefix, expires_at)
     }
 
     #[payable]
@@ -284,17 +497,26 @@ impl ChainKeyTokenApproval for NftKeyContract {
         &mut self,
         token_id: String,
         account_id: AccountId,
+        path_prefix: Option<String>,
+        expires_at: Option<u64>,
         msg: Option<String>,
     ) -> PromiseOrValue<Option<u32>> {
         assert_one_yocto();
         let predecessor = env::predecessor_account_id();
         self.require_is_token_owner(&predecessor, &token_id);
         let id = token_id.parse().expect_or_reject("Invalid token ID");
-        let approval_id = self.approve(id, &account_id);
+        let approval_id = self.approve(id, &account_id, path_prefix.clone(), expires_at);
 
         PromiseOrValue::Promise(
             ext_chain_key_token_approval_receiver::ext(account_id.clone())
-                .ckt_on_approved(predecessor, token_id, approval_id, msg.unwrap_or_default())
+                .ckt_on_approved(
+                    predecessor,
+                    token_id,
+                    approval_id,
+                    path_prefix,
+                    expires_at,
+                    msg.unwrap_or_default(),
+                )
                 .then(Self::ext(env::current_account_id()).ckt_approve_callback(
                     id,
                     account_id,
@@ -355,6 +577,93 @@ impl ChainKeyTokenApproval for NftKeyContract {
         self.key_data
             .get(&id)
             .and_then(|key_data| key_data.approvals.get(&account_id))
+            .map(|approval| approval.approval_id)
+    }
+}
+
+impl UpgradeHook for NftKeyContract {
+    fn on_upgrade(&self) {
+        self.require_role(&Role::Dao);
+    }
+}
+
+impl PauseHook for NftKeyContract {
+    fn on_pause(&self) {
+        self.require_role(&Role::SignerAdmin);
+    }
+
+    fn on_unpause(&self) {
+        self.require_role(&Role::SignerAdmin);
+    }
+}
+
+#[near]
+impl NftKeyContract {
+    /// Redeploys this contract from the WASM passed as the raw transaction input, then calls
+    /// `migrate` with whatever gas is left over from this call, passing along the current
+    /// owner so `migrate` can re-grant it the roles `new` would have granted.
+    pub fn upgrade(&self) {
+        self.on_upgrade();
+
+        let code = env::input().expect_or_reject("Missing contract code in input");
+        let migrate_args = near_sdk::serde_json::to_vec(&near_sdk::serde_json::json!({
+            "owner": self.owner,
+        }))
+        .unwrap_or_reject();
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(
+                "migrate".to_string(),
+                migrate_args,
+                NearToken::from_near(0),
+                env::prepaid_gas().saturating_sub(env::used_gas()),
+            );
+    }
+
+    /// Deserializes the pre-upgrade state layout and maps it onto the current one, re-granting
+    /// `owner` the roles `new` would have granted it. Called on the freshly-deployed code by the
+    /// promise batch that `upgrade` issues, so it must not assume `self` is set up.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(owner: AccountId) -> Self {
+        let old: OldState = env::state_read().expect_or_reject("Failed to read old state");
+
+        let mut key_data = UnorderedMap::new(StorageKey::KeyData);
+        for (id, old_data) in old.key_data.iter() {
+            let mut approvals = UnorderedMap::new(StorageKey::ApprovalsFor(id));
+            for (account_id, approval_id) in old_data.approvals.iter() {
+                approvals.insert(
+                    &account_id,
+                    &Approval {
+                        approval_id,
+                        path_prefix: None,
+                        expires_at: None,
+                    },
+                );
+            }
+
+            key_data.insert(
+                &id,
+                &KeyData {
+                    approvals,
+                    key_version: old_data.key_version,
+                    scheme: Scheme::Secp256k1,
+                },
+            );
+        }
+
+        let mut contract = Self {
+            next_id: old.next_id,
+            signer_contract_id: old.signer_contract_id,
+            key_data,
+            owner: owner.clone(),
+        };
+
+        contract.add_role(&owner, &Role::Dao);
+        contract.add_role(&owner, &Role::SignerAdmin);
+
+        contract
     }
 }
 